@@ -0,0 +1,121 @@
+use std::str::FromStr;
+
+use chrono::naive::NaiveDate;
+use rusqlite::{params, Connection, OptionalExtension};
+use rust_decimal::Decimal;
+
+/*
+ * SQLite-backed store for confirmed exchange rates, one row per (base, quote, date).
+ * Only rates confirmed on a business day are ever stored; the rate in effect on a
+ * weekend (or any other day with no quote of its own) is derived on read by
+ * forward-filling the most recent prior business-day rate, the same way banks
+ * treat weekend FX rates.
+ */
+pub struct RateCache {
+    conn: Connection,
+}
+
+impl RateCache {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| format!("cannot open rate cache {}: {}", path, e))?;
+        RateCache::from_connection(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory().map_err(|e| format!("cannot open in-memory rate cache: {}", e))?;
+        RateCache::from_connection(conn)
+    }
+
+    fn from_connection(conn: Connection) -> Result<Self, String> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS rates (
+                base TEXT NOT NULL,
+                quote TEXT NOT NULL,
+                date TEXT NOT NULL,
+                rate TEXT NOT NULL,
+                PRIMARY KEY (base, quote, date)
+            )",
+            [],
+        ).map_err(|e| format!("cannot create rates table: {}", e))?;
+        Ok(RateCache { conn })
+    }
+
+    //store a rate confirmed on a business day
+    pub fn store(&self, base: &str, quote: &str, date: &NaiveDate, rate: Decimal) -> Result<(), String> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO rates (base, quote, date, rate) VALUES (?1, ?2, ?3, ?4)",
+            params![base, quote, date.format("%F").to_string(), rate.to_string()],
+        ).map_err(|e| format!("cannot store rate: {}", e))?;
+        Ok(())
+    }
+
+    //the rate confirmed exactly on this date, with no forward-fill
+    pub fn get(&self, base: &str, quote: &str, date: &NaiveDate) -> Result<Option<Decimal>, String> {
+        parse_cached_rate(self.conn.query_row(
+            "SELECT rate FROM rates WHERE base = ?1 AND quote = ?2 AND date = ?3",
+            params![base, quote, date.format("%F").to_string()],
+            |row| row.get::<_, String>(0),
+        ).optional().map_err(|e| format!("cannot read rate: {}", e))?)
+    }
+
+    //the rate confirmed on this date, or, failing that, the most recent prior
+    //business-day rate carried forward
+    pub fn get_with_forward_fill(&self, base: &str, quote: &str, date: &NaiveDate) -> Result<Option<Decimal>, String> {
+        if let Some(rate) = self.get(base, quote, date)? {
+            return Ok(Some(rate));
+        }
+
+        parse_cached_rate(self.conn.query_row(
+            "SELECT rate FROM rates WHERE base = ?1 AND quote = ?2 AND date < ?3 ORDER BY date DESC LIMIT 1",
+            params![base, quote, date.format("%F").to_string()],
+            |row| row.get::<_, String>(0),
+        ).optional().map_err(|e| format!("cannot read rate: {}", e))?)
+    }
+}
+
+fn parse_cached_rate(raw: Option<String>) -> Result<Option<Decimal>, String> {
+    raw.map(|raw| Decimal::from_str(&raw).map_err(|e| format!("cannot parse cached rate value: {} {}", raw, e)))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_with_forward_fill() {
+        let cache = RateCache::open_in_memory().unwrap();
+
+        //nothing stored yet: no exact match, nothing to forward-fill from
+        assert_eq!(cache.get("AAA", "BBB", &NaiveDate::from_ymd(2021, 3, 6)).unwrap(), None);
+        assert_eq!(cache.get_with_forward_fill("AAA", "BBB", &NaiveDate::from_ymd(2021, 3, 6)).unwrap(), None);
+
+        cache.store("AAA", "BBB", &NaiveDate::from_ymd(2021, 3, 5), Decimal::from(5)).unwrap();
+
+        //a weekend right after a confirmed business day carries that rate forward
+        assert_eq!(
+            cache.get_with_forward_fill("AAA", "BBB", &NaiveDate::from_ymd(2021, 3, 6)).unwrap(),
+            Some(Decimal::from(5))
+        );
+        assert_eq!(
+            cache.get_with_forward_fill("AAA", "BBB", &NaiveDate::from_ymd(2021, 3, 7)).unwrap(),
+            Some(Decimal::from(5))
+        );
+
+        //a confirmed rate on the date itself is used as-is, not forward-filled
+        cache.store("AAA", "BBB", &NaiveDate::from_ymd(2021, 3, 8), Decimal::from(8)).unwrap();
+        assert_eq!(
+            cache.get_with_forward_fill("AAA", "BBB", &NaiveDate::from_ymd(2021, 3, 8)).unwrap(),
+            Some(Decimal::from(8))
+        );
+
+        //forward-fill always reaches back to the most recent confirmed rate, not just the previous day
+        assert_eq!(
+            cache.get_with_forward_fill("AAA", "BBB", &NaiveDate::from_ymd(2021, 3, 9)).unwrap(),
+            Some(Decimal::from(8))
+        );
+
+        //different currency pairs don't leak into each other's forward-fill
+        assert_eq!(cache.get_with_forward_fill("CCC", "DDD", &NaiveDate::from_ymd(2021, 3, 9)).unwrap(), None);
+    }
+}
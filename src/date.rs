@@ -0,0 +1,103 @@
+use chrono::naive::NaiveDate;
+use chrono::{Datelike, Duration, Local};
+
+/*
+ * Parses a CLI date argument. Accepts strict YYYY-MM-DD, the friendlier
+ * YYYY.MM.DD and DD.MM.YYYY, and relative tokens: "today", "yesterday", and
+ * "<n>d"/"<n>w"/"<n>m" for that many days/weeks/months before today, so a
+ * rolling window can be requested as e.g. `exchange USD GBP 30d today`.
+ */
+pub fn parse_date(raw: &str) -> Result<NaiveDate, String> {
+    let today = Local::now().naive_local().date();
+
+    match raw {
+        "today" => return Ok(today),
+        "yesterday" => return Ok(today - Duration::days(1)),
+        _ => {}
+    }
+
+    if let Some(date) = parse_relative_offset(raw, today) {
+        return Ok(date);
+    }
+
+    for format in &["%Y-%m-%d", "%Y.%m.%d", "%d.%m.%Y"] {
+        if let Ok(date) = NaiveDate::parse_from_str(raw, format) {
+            return Ok(date);
+        }
+    }
+
+    Err(format!("cannot parse date: {}", raw))
+}
+
+//"30d"/"3w"/"6m" -> that many days/weeks/months before today
+fn parse_relative_offset(raw: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let unit_len = raw.chars().last()?.len_utf8();
+    let (amount, unit) = raw.split_at(raw.len() - unit_len);
+    let amount: i64 = amount.parse().ok()?;
+
+    match unit {
+        "d" => today.checked_sub_signed(Duration::days(amount)),
+        "w" => today.checked_sub_signed(Duration::weeks(amount)),
+        "m" => Some(subtract_months(today, amount)),
+        _ => None
+    }
+}
+
+fn subtract_months(date: NaiveDate, months: i64) -> NaiveDate {
+    let total_months = date.year() as i64 * 12 + date.month() as i64 - 1 - months;
+    let year = total_months.div_euclid(12) as i32;
+    let month = total_months.rem_euclid(12) as u32 + 1;
+    //clamp the day so e.g. 31 March minus 1 month doesn't overflow into April
+    let day = date.day().min(days_in_month(year, month));
+
+    NaiveDate::from_ymd(year, month, day)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let next_month_first = if month == 12 {
+        NaiveDate::from_ymd(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd(year, month + 1, 1)
+    };
+
+    next_month_first.signed_duration_since(NaiveDate::from_ymd(year, month, 1)).num_days() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date_formats() {
+        assert_eq!(parse_date("2021-03-08"), Ok(NaiveDate::from_ymd(2021, 3, 8)));
+        assert_eq!(parse_date("2021.03.08"), Ok(NaiveDate::from_ymd(2021, 3, 8)));
+        assert_eq!(parse_date("08.03.2021"), Ok(NaiveDate::from_ymd(2021, 3, 8)));
+        assert!(parse_date("not a date").is_err());
+
+        //multi-byte UTF-8 input must not panic when checking for a relative-offset suffix
+        assert!(parse_date("日本語").is_err());
+
+        //a relative offset large enough to overflow chrono's representable date range
+        //must not panic when subtracted from today
+        assert!(parse_date("1000000000d").is_err());
+    }
+
+    #[test]
+    fn test_parse_date_relative_tokens() {
+        let today = Local::now().naive_local().date();
+
+        assert_eq!(parse_date("today"), Ok(today));
+        assert_eq!(parse_date("yesterday"), Ok(today - Duration::days(1)));
+        assert_eq!(parse_date("30d"), Ok(today - Duration::days(30)));
+        assert_eq!(parse_date("3w"), Ok(today - Duration::weeks(3)));
+    }
+
+    #[test]
+    fn test_subtract_months_clamps_short_months() {
+        //31 March minus 1 month would be invalid as 31 February, so it clamps to the last day of February
+        assert_eq!(subtract_months(NaiveDate::from_ymd(2021, 3, 31), 1), NaiveDate::from_ymd(2021, 2, 28));
+
+        //crossing a year boundary
+        assert_eq!(subtract_months(NaiveDate::from_ymd(2021, 1, 15), 2), NaiveDate::from_ymd(2020, 11, 15));
+    }
+}
@@ -12,56 +12,49 @@ FLAGS:
 ARGS:
     <CURRENCYFROM>
     <CURRENCYTO>
-    <DATEFROM>        date in format YYYY-MM-DD
-    <DATETO>          date in format YYYY-MM-DD
+    <DATEFROM>        date as YYYY-MM-DD, YYYY.MM.DD, DD.MM.YYYY, "today", "yesterday",
+                      or a relative offset like 30d/3w/6m
+    <DATETO>          same formats as <DATEFROM>
 
-obtained results will be cached in files in local directory
+obtained results will be cached in a local SQLite database (see rates.sqlite3)
+weekend rates are not fetched, they carry forward the last business day's rate
 small percentage of requests is allowed to fail, you'll see notice about that
 larget percentage or failures will show error
 */
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::Path;
+use std::str::FromStr;
 
 use chrono::Datelike;
 use chrono::naive::NaiveDate;
+use rust_decimal::prelude::MathematicalOps;
+use rust_decimal::Decimal;
 use structopt::StructOpt;
 
+mod cache;
+mod date;
 mod types;
 mod utils;
 
-use types::{Opt, ExchangeValue, ExchangeProvider, ExchangeResult};
+use cache::RateCache;
+use types::{Opt, ExchangeValue, ExchangeProviders, ExchangeResult};
 use utils::{reqwest_error_to_string, json_error_to_string};
 
 //if we cannot retrieve rates for more than this fraction of working days, exit with an error,
 //otherwise show notice, but produce result
 const ACCEPTABLE_RETRIEVAL_FAILURE_FRACTION: f64 = 0.05;
 const EXCHANGE_URL: &str = "https://api.exchangeratesapi.io/";
+const FRANKFURTER_URL: &str = "https://api.frankfurter.app/";
+const CACHE_DB_PATH: &str = "rates.sqlite3";
+
+//providers queried for every date; see get_median_rate
+const EXCHANGE_PROVIDERS: ExchangeProviders = &[get_exchange_rate, get_frankfurter_rate];
 
 /*
- * Retrieve exchange rates from external service or cache if available,
- * cache returned value in local file
+ * Retrieve an exchange rate from the external service.
+ * Caching (and weekend forward-fill) is handled by RateCache in exchange_rate_overview.
  */
-pub fn get_exchange_rate(currency_from: &str, currency_to: &str, date: &NaiveDate) -> Result<f64, String> {
+pub fn get_exchange_rate(currency_from: &str, currency_to: &str, date: &NaiveDate) -> Result<Decimal, String> {
 
     let formatted_date:String = date.format("%F").to_string();
-    //try to get cached results from a file
-    let fname = format!("{}_{}_{}.cached", currency_from, currency_to, formatted_date);
-    let path = Path::new(&fname);
-    if path.exists() {
-         let mut file = File::open(&path).map_err(|e| format!("cannot open cache file: {} {}",e ,fname))?;
-         let mut buffer = Vec::new();
-         file.read_to_end(&mut buffer).map_err(|e| format!("cannot read cache file: {} {}",e ,fname))?;
-         match std::str::from_utf8(buffer.as_slice()) {
-             Ok(txt) => {
-                 match txt.parse::<f64>() {
-                    Ok(value) => return Ok(value),
-                    Err(e) => return Err(format!("cannot parse cached rate value: {} {}", fname, e))
-                 }
-             }
-             Err(e) => return Err(format!("invalid cache file content: {} {}",fname, e))
-         }
-    }
 
     let client = reqwest::blocking::ClientBuilder::new()
         .timeout(std::time::Duration::from_secs(10))
@@ -80,7 +73,7 @@ pub fn get_exchange_rate(currency_from: &str, currency_to: &str, date: &NaiveDat
 
 
     //{"rates":{"USD":1.0,"GBP":0.7224675544},"base":"USD","date":"2021-03-08"} -> 0.7224675544
-    let rate_value: f64 = json_body
+    let rate_value: Decimal = json_body
         .as_object()
         .ok_or_else(|| json_error_to_string(&json_body))?
         .get("rates")
@@ -88,23 +81,126 @@ pub fn get_exchange_rate(currency_from: &str, currency_to: &str, date: &NaiveDat
         .as_object()
         .ok_or_else(|| json_error_to_string(&json_body))?
         .get(currency_to)
-        .ok_or_else(|| json_error_to_string(&json_body))?
-        .as_f64()
-        .ok_or_else(|| json_error_to_string(&json_body))?;
+        .ok_or_else(|| json_error_to_string(&json_body))
+        .and_then(|v| Decimal::from_str(&v.to_string()).map_err(|_| json_error_to_string(&json_body)))?;
+
+    Ok(rate_value)
+}
+
+/*
+ * A second backend with a different response shape, see get_median_rate
+ */
+pub fn get_frankfurter_rate(currency_from: &str, currency_to: &str, date: &NaiveDate) -> Result<Decimal, String> {
+
+    let formatted_date: String = date.format("%F").to_string();
+
+    let client = reqwest::blocking::ClientBuilder::new()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .map_err(reqwest_error_to_string)?;
+    let json_body: serde_json::Value = client
+        .get(&format!("{}{}", &FRANKFURTER_URL, &formatted_date))
+        .query(&[
+            ("from", currency_from.to_string()),
+            ("to", currency_to.to_string()),
+        ])
+        .send()
+        .map_err(reqwest_error_to_string)?
+        .json()
+        .map_err(reqwest_error_to_string)?;
 
-    //cache value
-    let mut file = File::create(&path).map_err(|e| format!("cannot create cache file: {:?} {}", path, e))?;
-    file.write_all(rate_value.to_string().as_bytes())
-        .map_err(|e| format!("cannot write cache file: {:?} {}", path, e))?;
+    //{"amount":1.0,"base":"USD","date":"2021-03-08","rates":{"GBP":0.7224675544}} -> 0.7224675544
+    let rate_value: Decimal = json_body
+        .as_object()
+        .ok_or_else(|| json_error_to_string(&json_body))?
+        .get("rates")
+        .ok_or_else(|| json_error_to_string(&json_body))?
+        .as_object()
+        .ok_or_else(|| json_error_to_string(&json_body))?
+        .get(currency_to)
+        .ok_or_else(|| json_error_to_string(&json_body))
+        .and_then(|v| Decimal::from_str(&v.to_string()).map_err(|_| json_error_to_string(&json_body)))?;
 
     Ok(rate_value)
 }
 
+//the median of a non-empty set of values
+fn median_of(mut values: Vec<Decimal>) -> Decimal {
+    values.sort();
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / Decimal::from(2)
+    } else {
+        values[mid]
+    }
+}
+
+/*
+ * Query every configured provider for a single date, collect whatever succeeds
+ * and return the median so one outage or outlier quote doesn't sink the day.
+ * Only errors out if zero providers produced a value.
+ */
+pub fn get_median_rate(exchange_providers: ExchangeProviders, currency_from: &str, currency_to: &str, date: &NaiveDate) -> Result<Decimal, String> {
+
+    let rates: Vec<Decimal> = exchange_providers
+        .iter()
+        .filter_map(|provider| provider(currency_from, currency_to, date).ok())
+        .collect();
+
+    if rates.is_empty() {
+        return Err("no provider returned a rate for this date".to_string());
+    }
+
+    Ok(median_of(rates))
+}
+
+/*
+ * Whether a date's rate is a confirmed business-day quote (live or cached) or
+ * one carried forward from a prior business day. Forward-filling a gap on a
+ * business day (every provider down) is still useful for the running average,
+ * but -- unlike a weekend -- it must not be mistaken for a retrieved quote by
+ * the ACCEPTABLE_RETRIEVAL_FAILURE_FRACTION check in exchange_rate_overview.
+ */
+enum RateOutcome {
+    Confirmed(Decimal),
+    ForwardFilled(Decimal),
+}
+
+/*
+ * The rate in effect for a single date: on a business day this is the (cached or
+ * freshly fetched) median of all providers, confirmed. On a weekend, or on a
+ * business day where every provider is down, it falls back to the most recent
+ * prior business-day rate, carried forward like an official bank rate.
+ */
+fn rate_for_date(opt: &Opt, exchange_providers: ExchangeProviders, cache: &RateCache, date: &NaiveDate, is_business_day: bool) -> Result<RateOutcome, String> {
+    if !is_business_day {
+        return cache
+            .get_with_forward_fill(&opt.currency_from, &opt.currency_to, date)?
+            .map(RateOutcome::ForwardFilled)
+            .ok_or_else(|| "no prior business-day rate available to carry forward".to_string());
+    }
+
+    if let Some(value) = cache.get(&opt.currency_from, &opt.currency_to, date)? {
+        return Ok(RateOutcome::Confirmed(value));
+    }
+
+    match get_median_rate(exchange_providers, &opt.currency_from, &opt.currency_to, date) {
+        Ok(value) => {
+            cache.store(&opt.currency_from, &opt.currency_to, date, value)?;
+            Ok(RateOutcome::Confirmed(value))
+        }
+        Err(err_str) => cache
+            .get_with_forward_fill(&opt.currency_from, &opt.currency_to, date)?
+            .map(RateOutcome::ForwardFilled)
+            .ok_or(err_str),
+    }
+}
+
 /*
  *  Obtain exchange rates for given currencies and data range
- *  to make testing easier, exchanges are obtained via ExchangeProvider
+ *  to make testing easier, exchanges are obtained via ExchangeProviders
  * */
-pub fn exchange_rate_overview(opt: &Opt, exchange_provider: ExchangeProvider) -> ExchangeResult {
+pub fn exchange_rate_overview(opt: &Opt, exchange_providers: ExchangeProviders, cache: &RateCache) -> ExchangeResult {
     if opt.currency_from.to_lowercase() == opt.currency_to.to_lowercase() {
         return Err("You have to pick two different currencies".to_string());
     }
@@ -113,69 +209,97 @@ pub fn exchange_rate_overview(opt: &Opt, exchange_provider: ExchangeProvider) ->
     if date_diff.num_days() < 0 {
         return Err("date_from must precede or be equal to date_to".to_string());
     }
- 
+
     let mut processed_date = opt.date_from;
-    let mut expected_days = 0; // amount of Mon-Fri days we expect to get data for
-    let mut retrieved_days = 0; // amount of days we retrieved data for
+    let mut expected_days = 0; // amount of Mon-Fri days we expect to get a rate for
+    let mut retrieved_business_days = 0; // amount of Mon-Fri days we retrieved a rate for, confirmed or carried forward
+    let mut retrieved_days = 0; // amount of days (business days and forward-filled weekends) included in the average
 
-    let mut rate_sum: f64 = 0f64;
-    let mut max_rate: Option<(f64, NaiveDate)> = None;
-    let mut min_rate: Option<(f64, NaiveDate)> = None;
+    let mut rate_sum: Decimal = Decimal::ZERO;
+    let mut max_rate: Option<(Decimal, NaiveDate)> = None;
+    let mut min_rate: Option<(Decimal, NaiveDate)> = None;
+    let mut retrieved_values: Vec<Decimal> = Vec::new(); // in date order, for std-dev/pct-change/median
 
 
     while opt.date_to.signed_duration_since(processed_date).num_days() >= 0 {
-        match processed_date.weekday() {
-            chrono::Weekday::Sat | chrono::Weekday::Sun => {},
-            _ => {
-                expected_days += 1;
-                match exchange_provider(&opt.currency_from, &opt.currency_to, &processed_date) {
-                    Ok(value) => {
-                        retrieved_days += 1;
-                        rate_sum += value;
-                        max_rate = match max_rate {
-                            Some(v) => if value > v.0 {
-                                Some((value, processed_date))
-                            } else {
-                                Some(v)
-                            },
-                            None => Some((value, processed_date))
-                        };
-                        min_rate = match min_rate {
-                            Some(v) => if value < v.0 {
-                                Some((value, processed_date))
-                            } else {
-                                Some(v)
-                            },
-                            None => Some((value, processed_date))
-                        };
-                    },
-                    Err(err_str) => {
-                        eprintln!("{} -> Failed to retrieve rates: {}", &processed_date, err_str);
+        let is_business_day = !matches!(processed_date.weekday(), chrono::Weekday::Sat | chrono::Weekday::Sun);
+        if is_business_day {
+            expected_days += 1;
+        }
+
+        match rate_for_date(opt, exchange_providers, cache, &processed_date, is_business_day) {
+            Ok(outcome) => {
+                let value = match outcome {
+                    RateOutcome::Confirmed(value) => {
+                        if is_business_day {
+                            retrieved_business_days += 1;
+                        }
+                        value
                     }
-                }
+                    //carried forward: still counted in the running average, but not
+                    //towards retrieved_business_days, so a business-day outage still
+                    //shows up in the ACCEPTABLE_RETRIEVAL_FAILURE_FRACTION check
+                    RateOutcome::ForwardFilled(value) => value,
+                };
+                retrieved_days += 1;
+                rate_sum += value;
+                retrieved_values.push(value);
+                max_rate = match max_rate {
+                    Some(v) => if value > v.0 {
+                        Some((value, processed_date))
+                    } else {
+                        Some(v)
+                    },
+                    None => Some((value, processed_date))
+                };
+                min_rate = match min_rate {
+                    Some(v) => if value < v.0 {
+                        Some((value, processed_date))
+                    } else {
+                        Some(v)
+                    },
+                    None => Some((value, processed_date))
+                };
+            },
+            Err(err_str) => {
+                eprintln!("{} -> Failed to retrieve rates: {}", &processed_date, err_str);
             }
         }
 
         processed_date += chrono::Duration::days(1);
     }
 
-    if expected_days == 0 || retrieved_days == 0 {
+    if expected_days == 0 || retrieved_business_days == 0 {
         return Err("Could not retrieve even 1 rate. Perhaps pick a date range with more working days.".to_string());
     }
 
-    if (1f64-(retrieved_days as f64/expected_days as f64)) > ACCEPTABLE_RETRIEVAL_FAILURE_FRACTION {
+    if (1f64-(retrieved_business_days as f64/expected_days as f64)) > ACCEPTABLE_RETRIEVAL_FAILURE_FRACTION {
         return Err(format!("Failure rate exceeded acceptable threshold ({})", ACCEPTABLE_RETRIEVAL_FAILURE_FRACTION));
     }
 
 
+    let mean_rate = rate_sum / Decimal::from(retrieved_days);
+    let variance: Decimal = retrieved_values.iter()
+        .map(|value| (value - mean_rate) * (value - mean_rate))
+        .sum::<Decimal>() / Decimal::from(retrieved_days);
+    let first_value = *retrieved_values.first().unwrap();
+    let last_value = *retrieved_values.last().unwrap();
+
+    if first_value.is_zero() {
+        return Err("cannot compute percentage change: the first retrieved rate was zero".to_string());
+    }
+
     Ok(ExchangeValue {
-        mean_rate: rate_sum as f64/retrieved_days as f64,
+        mean_rate,
+        median_rate: median_of(retrieved_values),
         min_rate: min_rate.unwrap(),
         max_rate: max_rate.unwrap(),
-        notice: if expected_days == retrieved_days {
+        std_dev: variance.sqrt().unwrap_or(Decimal::ZERO),
+        pct_change: (last_value - first_value) / first_value * Decimal::from(100),
+        notice: if expected_days == retrieved_business_days {
             None
         } else {
-            Some(format!("we failed to retrieve {} of {} rates", expected_days-retrieved_days, expected_days))
+            Some(format!("we failed to retrieve {} of {} rates", expected_days-retrieved_business_days, expected_days))
         }
     })
 }
@@ -185,7 +309,15 @@ fn main() {
     let opt = Opt::from_args();
     println!("For {}:{} between {} and {}", opt.currency_from, opt.currency_to, opt.date_from, opt.date_to);
 
-    match exchange_rate_overview(&opt, get_exchange_rate) {
+    let cache = match RateCache::open(CACHE_DB_PATH) {
+        Ok(cache) => cache,
+        Err(err_str) => {
+            eprintln!("{}", err_str);
+            std::process::exit(1);
+        }
+    };
+
+    match exchange_rate_overview(&opt, EXCHANGE_PROVIDERS, &cache) {
         Ok(exchange_result) => println!("{:#?}", exchange_result),
         Err(err_str) => {
             eprintln!("{}", err_str);
@@ -211,11 +343,14 @@ mod tests {
         };
 
         assert_eq!(
-            exchange_rate_overview(&opt, |_, _, _| { Ok(1f64) }),
+            exchange_rate_overview(&opt, &[|_, _, _| { Ok(Decimal::from(1)) }], &RateCache::open_in_memory().unwrap()),
             Ok(ExchangeValue {
-                mean_rate: 1f64,
-                min_rate: (1f64, opt.date_from),
-                max_rate: (1f64, opt.date_to), 
+                mean_rate: Decimal::from(1),
+                median_rate: Decimal::from(1),
+                min_rate: (Decimal::from(1), opt.date_from),
+                max_rate: (Decimal::from(1), opt.date_to),
+                std_dev: Decimal::ZERO,
+                pct_change: Decimal::ZERO,
                 notice: None
             })
         );
@@ -225,7 +360,7 @@ mod tests {
         opt.date_to = NaiveDate::from_ymd(2021, 3, 6); //Sat
 
         assert_eq!(
-            exchange_rate_overview(&opt, |_, _, _| { Ok(1f64) }),
+            exchange_rate_overview(&opt, &[|_, _, _| { Ok(Decimal::from(1)) }], &RateCache::open_in_memory().unwrap()),
             Err("Could not retrieve even 1 rate. Perhaps pick a date range with more working days.".to_string())
         );
 
@@ -235,35 +370,46 @@ mod tests {
 
         assert_eq!(
             //5 days, rates values are 1..5
-            exchange_rate_overview(&opt, |_, _, date| { Ok(date.day() as f64) }),
+            exchange_rate_overview(&opt, &[|_, _, date| { Ok(Decimal::from(date.day())) }], &RateCache::open_in_memory().unwrap()),
             Ok(ExchangeValue {
-                mean_rate: 3.0,
-                min_rate: (1f64, opt.date_from),
-                max_rate: (5f64, opt.date_to), 
+                mean_rate: Decimal::from(3),
+                median_rate: Decimal::from(3),
+                min_rate: (Decimal::from(1), opt.date_from),
+                max_rate: (Decimal::from(5), opt.date_to),
+                std_dev: Decimal::from_str("1.4142135623730950488016887242").unwrap(),
+                pct_change: Decimal::from(400),
                 notice: None
             })
         );
 
-        //test that asking for 46 working days when 1 fails shows notice
+        //test that asking for 46 working days when 1 fails shows notice; the range also
+        //covers several weekends, which forward-fill the preceding business day's rate
+        //and so count towards the mean too. The one failed business day (Mar 5) also
+        //forward-fills Mar 4's rate into the mean, but still counts as not-retrieved
+        //for the notice, since the outage -- unlike a weekend -- is a real gap
         opt.date_from = NaiveDate::from_ymd(2021, 1, 1);
-        opt.date_to = NaiveDate::from_ymd(2021, 3, 5); 
+        opt.date_to = NaiveDate::from_ymd(2021, 3, 5);
 
         assert_eq!(
-            //46 days, rates values 1..31, 1..28, 1..4, Error
+            //46 business days, rates values 1..31, 1..28, 1..4, Error (Mar 5 forward-fills Mar 4's 4)
             exchange_rate_overview(
                 &opt,
-                |_, _, date| {
+                &[|_, _, date| {
                     if (date.month() == 3 && date.day() == 5) {
                         Err("error".to_string())
                     } else  {
-                        Ok(date.day() as f64)
+                        Ok(Decimal::from(date.day()))
                     }
-                }
+                }],
+                &RateCache::open_in_memory().unwrap()
             ),
             Ok(ExchangeValue {
-                mean_rate: 13.577777777777778,
-                min_rate: (1f64, opt.date_from),
-                max_rate: (29f64, NaiveDate::from_ymd(2021, 1, 29)),
+                mean_rate: Decimal::from_str("13.890625").unwrap(),
+                median_rate: Decimal::from_str("13.50").unwrap(),
+                min_rate: (Decimal::from(1), opt.date_from),
+                max_rate: (Decimal::from(29), NaiveDate::from_ymd(2021, 1, 29)),
+                std_dev: Decimal::from_str("8.803616422208262022115882852").unwrap(),
+                pct_change: Decimal::from(300),
                 notice: Some("we failed to retrieve 1 of 46 rates".to_string())
             })
         );
@@ -271,21 +417,51 @@ mod tests {
 
         //test that exceeding error margin fails
         opt.date_from = NaiveDate::from_ymd(2021, 1, 1);
-        opt.date_to = NaiveDate::from_ymd(2021, 3, 5); 
+        opt.date_to = NaiveDate::from_ymd(2021, 3, 5);
 
         assert_eq!(
-            //46 days, rates values 1..31, 1..28, 5*Error
+            //46 business days, rates values 1..31, 1..28, 5*Error
             exchange_rate_overview(
                 &opt,
-                |_, _, date| {
+                &[|_, _, date| {
                     if (date.month() == 3 ) {
                         Err("error".to_string())
                     } else  {
-                        Ok(date.day() as f64)
+                        Ok(Decimal::from(date.day()))
                     }
-                }
+                }],
+                &RateCache::open_in_memory().unwrap()
             ),
             Err(format!("Failure rate exceeded acceptable threshold ({})", ACCEPTABLE_RETRIEVAL_FAILURE_FRACTION))
         );
     }
+
+    #[test]
+    fn test_get_median_rate() {
+        let date = NaiveDate::from_ymd(2021, 3, 1);
+
+        //odd number of providers: median is the middle value
+        assert_eq!(
+            get_median_rate(&[|_, _, _| Ok(Decimal::from(1)), |_, _, _| Ok(Decimal::from(2)), |_, _, _| Ok(Decimal::from(9))], "AAA", "BBB", &date),
+            Ok(Decimal::from(2))
+        );
+
+        //even number of providers: median is the mean of the two middle values
+        assert_eq!(
+            get_median_rate(&[|_, _, _| Ok(Decimal::from(1)), |_, _, _| Ok(Decimal::from(3))], "AAA", "BBB", &date),
+            Ok(Decimal::from(2))
+        );
+
+        //a failing provider is excluded rather than failing the whole lookup
+        assert_eq!(
+            get_median_rate(&[|_, _, _| Ok(Decimal::from(5)), |_, _, _| Err("down".to_string())], "AAA", "BBB", &date),
+            Ok(Decimal::from(5))
+        );
+
+        //only an error if every provider fails
+        assert_eq!(
+            get_median_rate(&[|_, _, _| Err("down".to_string())], "AAA", "BBB", &date),
+            Err("no provider returned a rate for this date".to_string())
+        );
+    }
 }
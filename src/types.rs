@@ -1,26 +1,35 @@
 use chrono::naive::NaiveDate;
+use rust_decimal::Decimal;
 use structopt::StructOpt;
 
+use crate::date::parse_date;
+
 #[derive(Debug, StructOpt)]
 #[structopt(rename_all = "uppercase")]
 #[structopt(about = "Use exchangeratesapi.io to get exchange rates for given time period")]
 pub struct Opt {
     pub currency_from: String,
     pub currency_to: String,
-    #[structopt(help = "date in format YYYY-MM-DD")]
+    #[structopt(help = "date as YYYY-MM-DD, YYYY.MM.DD, DD.MM.YYYY, \"today\", \"yesterday\", or a relative offset like 30d/3w/6m", parse(try_from_str = parse_date))]
     pub date_from: NaiveDate,
-    #[structopt(help = "date in format YYYY-MM-DD")]
+    #[structopt(help = "date as YYYY-MM-DD, YYYY.MM.DD, DD.MM.YYYY, \"today\", \"yesterday\", or a relative offset like 30d/3w/6m", parse(try_from_str = parse_date))]
     pub date_to: NaiveDate,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct ExchangeValue {
-    pub mean_rate: f64,
-    pub min_rate: (f64, NaiveDate),
-    pub max_rate: (f64, NaiveDate),
+    pub mean_rate: Decimal,
+    pub median_rate: Decimal,
+    pub min_rate: (Decimal, NaiveDate),
+    pub max_rate: (Decimal, NaiveDate),
+    pub std_dev: Decimal, //population standard deviation of the daily rates
+    pub pct_change: Decimal, //percentage change from the first to the last retrieved day
     pub notice: Option<String> //optional notice for users
 }
 
 pub type ExchangeResult = Result<ExchangeValue, String>;
 
-pub type ExchangeProvider = fn (currency_from: &str, currency_to: &str, date: &NaiveDate) -> Result<f64, String>;
+pub type ExchangeProvider = fn (currency_from: &str, currency_to: &str, date: &NaiveDate) -> Result<Decimal, String>;
+
+//providers to query for a rate; see get_median_rate for how they're combined
+pub type ExchangeProviders<'a> = &'a [ExchangeProvider];